@@ -0,0 +1,319 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use libc::c_uint;
+use llvm;
+use llvm::{Integer, Pointer, Float, Double, Struct, Array, Vector};
+use llvm::{StructRetAttribute, ZExtAttribute};
+use trans::cabi::{FnType, ArgType};
+use trans::context::CrateContext;
+use trans::type_::Type;
+
+use std::cmp;
+
+// The ABI flavor this module is classifying for. `O32` is the traditional
+// 32-bit MIPS convention (4-byte pointers and general registers); `N64` is
+// the LP64-style 64-bit convention used by mips64/mips64el targets (8-byte
+// pointers and general registers). Everything below is parameterized over
+// this so the two conventions can share the same classification logic.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Flavor {
+    O32,
+    N64,
+}
+
+impl Flavor {
+    // Size in bytes of a pointer and of a general-purpose argument register.
+    fn word_size(&self) -> uint {
+        match *self {
+            Flavor::O32 => 4,
+            Flavor::N64 => 8,
+        }
+    }
+}
+
+fn align_up_to(off: uint, a: uint) -> uint {
+    return (off + a - 1) / a * a;
+}
+
+// Smallest power of two that is `>= x`, used to derive the natural
+// alignment of a vector type from its total size.
+fn next_power_of_two(x: uint) -> uint {
+    let mut p = 1u;
+    while p < x {
+        p *= 2;
+    }
+    p
+}
+
+// Number of elements in a vector type. `LLVMGetArrayLength` does an
+// unchecked `cast<ArrayType>` internally, so it must not be used on a
+// `VectorType` — use the vector-specific accessor instead.
+fn vector_length(ty: Type) -> uint {
+    unsafe {
+        llvm::LLVMGetVectorSize(ty.to_ref()) as uint
+    }
+}
+
+fn align(off: uint, ty: Type, flavor: Flavor) -> uint {
+    let a = ty_align(ty, flavor);
+    return align_up_to(off, a);
+}
+
+fn ty_align(ty: Type, flavor: Flavor) -> uint {
+    match ty.kind() {
+        Integer => {
+            unsafe {
+                ((llvm::LLVMGetIntTypeWidth(ty.to_ref()) as uint) + 7) / 8
+            }
+        }
+        Pointer => flavor.word_size(),
+        Float => 4,
+        Double => 8,
+        Struct => {
+          if ty.is_packed() {
+            1
+          } else {
+            let str_tys = ty.field_types();
+            str_tys.iter().fold(1, |a, t| cmp::max(a, ty_align(*t, flavor)))
+          }
+        }
+        Array => {
+            let elt = ty.element_type();
+            ty_align(elt, flavor)
+        }
+        Vector => {
+            // A vector's alignment is the natural (power-of-two) alignment
+            // of its overall size, not just its element's alignment, e.g.
+            // a 16-byte `<4 x i32>` aligns to 16 rather than 4.
+            next_power_of_two(ty_size(ty, flavor))
+        }
+        _ => panic!("ty_align: unhandled type")
+    }
+}
+
+fn ty_size(ty: Type, flavor: Flavor) -> uint {
+    match ty.kind() {
+        Integer => {
+            unsafe {
+                ((llvm::LLVMGetIntTypeWidth(ty.to_ref()) as uint) + 7) / 8
+            }
+        }
+        Pointer => flavor.word_size(),
+        Float => 4,
+        Double => 8,
+        Struct => {
+            if ty.is_packed() {
+                let str_tys = ty.field_types();
+                str_tys.iter().fold(0, |s, t| s + ty_size(*t, flavor))
+            } else {
+                let str_tys = ty.field_types();
+                let size = str_tys.iter().fold(0, |s, t| {
+                    align(s, *t, flavor) + ty_size(*t, flavor)
+                });
+                align_up_to(size, ty_align(ty, flavor))
+            }
+        }
+        Array => {
+            let len = ty.array_length();
+            let elt = ty.element_type();
+            let eltsz = ty_size(elt, flavor);
+            len * eltsz
+        }
+        Vector => {
+            let len = vector_length(ty);
+            let elt = ty.element_type();
+            let eltsz = ty_size(elt, flavor);
+            len * eltsz
+        }
+        _ => panic!("ty_size: unhandled type")
+    }
+}
+
+fn classify_ret_ty(ccx: &CrateContext, ty: Type, hardfloat: bool) -> ArgType {
+    // `float_reg_count` also matches a struct of one or two floats/doubles,
+    // not just a bare `Float`/`Double`; under O32 hard-float such a struct
+    // returns in `$f0`/`$f2` the same as a scalar, so routing it direct
+    // here too is intentional rather than an oversight.
+    if hardfloat && float_reg_count(ty).is_some() {
+        return ArgType::direct(ty, None, None, None);
+    }
+
+    if is_reg_ty(ty) {
+        let attr = if ty == Type::i1(ccx) { Some(ZExtAttribute) } else { None };
+        ArgType::direct(ty, None, None, attr)
+    } else {
+        ArgType::indirect(ty, Some(StructRetAttribute))
+    }
+}
+
+fn classify_arg_ty(ccx: &CrateContext,
+                    ty: Type,
+                    offset: &mut uint,
+                    flavor: Flavor,
+                    hardfloat: bool,
+                    fp_offset: &mut uint,
+                    int_seen: &mut bool) -> ArgType {
+    let orig_offset = *offset;
+    let size = ty_size(ty, flavor) * 8;
+    let mut align = ty_align(ty, flavor);
+
+    align = match flavor {
+        // O32 clamps alignment to between one 4-byte register and an
+        // 8-byte register pair: smaller alignments widen to 4, and larger
+        // ones (e.g. a 16-byte-aligned vector) narrow to 8.
+        Flavor::O32 => cmp::min(cmp::max(align, 4), 8),
+        // N64 registers are always 8 bytes wide, so every argument slot is
+        // 8-byte aligned regardless of the type's own alignment.
+        Flavor::N64 => 8,
+    };
+    *offset = align_up_to(*offset, align);
+    *offset += align_up_to(size, align * 8) / 8;
+
+    // O32 hard-float ties the floating-point argument registers ($f12,
+    // $f14) to the first two argument slots, so a float only lands there
+    // if no preceding argument has already claimed a general-purpose
+    // register and a slot is still free.
+    if hardfloat && flavor == Flavor::O32 && !*int_seen {
+        if let Some(n) = float_reg_count(ty) {
+            if *fp_offset + n <= 2 {
+                *fp_offset += n;
+                return ArgType::direct(ty, None, None, None);
+            }
+        }
+    }
+    *int_seen = true;
+
+    if is_reg_ty(ty) {
+        let attr = if ty == Type::i1(ccx) { Some(ZExtAttribute) } else { None };
+        ArgType::direct(ty, None, None, attr)
+    } else {
+        ArgType::direct(
+            ty,
+            Some(struct_ty(ccx, ty, flavor)),
+            padding_ty(ccx, align, orig_offset, flavor),
+            None
+        )
+    }
+}
+
+fn is_reg_ty(ty: Type) -> bool {
+    return match ty.kind() {
+        Integer
+        | Pointer
+        | Float
+        | Double => true,
+        _ => false
+    };
+}
+
+fn is_float_ty(ty: Type) -> bool {
+    match ty.kind() {
+        Float | Double => true,
+        _ => false
+    }
+}
+
+// Number of FP argument/return registers a type would consume under the
+// O32 hard-float convention: a scalar float/double takes one, and a
+// (non-packed) struct made up solely of one or two float/double fields
+// takes one per field so it can be split across `$f12`/`$f14`.
+fn float_reg_count(ty: Type) -> Option<uint> {
+    match ty.kind() {
+        Float | Double => Some(1),
+        Struct if !ty.is_packed() => {
+            let fields = ty.field_types();
+            if fields.len() >= 1 && fields.len() <= 2 &&
+               fields.iter().all(|f| is_float_ty(*f)) {
+                Some(fields.len())
+            } else {
+                None
+            }
+        }
+        _ => None
+    }
+}
+
+fn padding_ty(ccx: &CrateContext, align: uint, offset: uint, flavor: Flavor) -> Option<Type> {
+    if ((align - 1 ) & offset) > 0 {
+        match flavor {
+            Flavor::O32 => Some(Type::i32(ccx)),
+            Flavor::N64 => Some(Type::i64(ccx)),
+        }
+    } else {
+        None
+    }
+}
+
+// Coerce a `size`-bit aggregate into a sequence of general-register-sized
+// integers, with a final smaller integer for any non-register-sized
+// remainder so small aggregates still land entirely in argument registers
+// instead of spilling unnecessarily to the stack.
+fn coerce_to_int(ccx: &CrateContext, size: uint, flavor: Flavor) -> Vec<Type> {
+    let word_bits = flavor.word_size() * 8;
+    let int_ty = match flavor {
+        Flavor::O32 => Type::i32(ccx),
+        Flavor::N64 => Type::i64(ccx),
+    };
+    let mut args = Vec::new();
+
+    let mut n = size / word_bits;
+    while n > 0 {
+        args.push(int_ty);
+        n -= 1;
+    }
+
+    let r = size % word_bits;
+    if r > 0 {
+        unsafe {
+            args.push(Type::from_ref(llvm::LLVMIntTypeInContext(ccx.llcx(), r as c_uint)));
+        }
+    }
+
+    args
+}
+
+fn struct_ty(ccx: &CrateContext, ty: Type, flavor: Flavor) -> Type {
+    let size = ty_size(ty, flavor) * 8;
+    Type::struct_(ccx, coerce_to_int(ccx, size, flavor).as_slice(), false)
+}
+
+pub fn compute_abi_info(ccx: &CrateContext,
+                        atys: &[Type],
+                        rty: Type,
+                        ret_def: bool,
+                        flavor: Flavor,
+                        hardfloat: bool) -> FnType {
+    let ret_ty = if ret_def {
+        classify_ret_ty(ccx, rty, hardfloat)
+    } else {
+        ArgType::direct(Type::void(ccx), None, None, None)
+    };
+
+    let sret = ret_ty.is_indirect();
+    let mut arg_tys = Vec::new();
+    let mut offset = if sret { flavor.word_size() } else { 0 };
+    let mut fp_offset = 0u;
+    // The hidden `sret` pointer occupies the first general-purpose
+    // register, so it counts as an integer argument for the purposes of
+    // the hard-float FP-register-to-argument-position rule.
+    let mut int_seen = sret;
+
+    for aty in atys {
+        let ty = classify_arg_ty(ccx, *aty, &mut offset, flavor, hardfloat,
+                                  &mut fp_offset, &mut int_seen);
+        arg_tys.push(ty);
+    };
+
+    return FnType {
+        arg_tys: arg_tys,
+        ret_ty: ret_ty,
+    };
+}